@@ -1,31 +1,59 @@
 use anyhow::{anyhow, Context, Result};
-use arboard::{Clipboard, ImageData};
+use arboard::Clipboard;
 use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine as _};
 use clap::Parser;
-use image::{ImageBuffer, Rgba};
+use image::{imageops::FilterType, ImageBuffer, Rgba};
 use log::{debug, error, info, warn};
-use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use std::{
-    borrow::Cow,
+    collections::HashMap,
     fs,
-    io::Cursor,
+    io::{Cursor, Read, Write as _},
     path::{Path, PathBuf},
-    time::Duration,
 };
 
+use provider::ProviderKind;
+
+mod provider;
+
 // --- Configuration ---
 
 const APP_NAME: &str = "clippy-clippy";
 const CONFIG_FILE_NAME: &str = "config.yaml";
 
+/// Vision APIs bill and cap by resolution, so downscale anything larger than this by
+/// default; 2048px is comfortably above what any of the supported providers need for OCR.
+const DEFAULT_MAX_IMAGE_DIMENSION: u32 = 2048;
+
+/// Retry attempts used when `max_retries` is unset in the config file.
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
 #[derive(Deserialize, Debug)]
-struct Config {
+pub(crate) struct Config {
     api_url: String,
     api_token: String,
     model_name: Option<String>,
     max_tokens: Option<u32>,
     request_timeout_seconds: Option<u64>,
+    #[serde(default)]
+    provider: ProviderKind,
+    max_image_dimension: Option<u32>,
+    detail: Option<String>,
+    proxy: Option<String>,
+    max_retries: Option<u32>,
+}
+
+/// The on-disk shape of `config.yaml`: either a single flat profile (the legacy layout,
+/// kept for backward compatibility) or a `profiles:` map of named ones to pick from with
+/// `--profile` or `default_profile:`.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ConfigFile {
+    Profiles {
+        profiles: HashMap<String, Config>,
+        default_profile: Option<String>,
+    },
+    Flat(Config),
 }
 
 fn get_config_path() -> Result<PathBuf> {
@@ -42,7 +70,7 @@ fn get_config_path() -> Result<PathBuf> {
     Ok(config_dir.join(CONFIG_FILE_NAME))
 }
 
-fn load_config(config_path: &Path) -> Result<Config> {
+fn load_config(config_path: &Path, profile_name: Option<&str>) -> Result<Config> {
     if !config_path.exists() {
         // Create a default config file if it doesn't exist
         let default_config_content = r#"# Configuration for clippy-clippy
@@ -58,6 +86,36 @@ api_token: "YOUR_API_TOKEN_HERE"
 
 # Optional: Set HTTP request timeout in seconds (defaults to 60 if unset)
 # request_timeout_seconds: 60
+
+# Optional: Select the API backend: "openai" or "anthropic" (defaults to openai if unset)
+# provider: "openai"
+
+# Optional: Downscale images whose longest edge exceeds this many pixels (defaults to 2048 if unset)
+# max_image_dimension: 2048
+
+# Optional: Vision detail level to request: "low", "high", or "auto" (provider-dependent; defaults to "high" if unset)
+# detail: "high"
+
+# Optional: HTTP(S) proxy URL (falls back to the HTTP_PROXY/HTTPS_PROXY env vars if unset)
+# proxy: "http://proxy.example.com:8080"
+
+# Optional: Max retries with exponential backoff on connection errors, timeouts, and 429/5xx responses (defaults to 3 if unset)
+# max_retries: 3
+
+# Instead of the flat layout above, you can define multiple named profiles and switch
+# between them with --profile <name>:
+#
+# default_profile: "openai"
+# profiles:
+#   openai:
+#     api_url: "https://api.openai.com/v1/chat/completions"
+#     api_token: "YOUR_OPENAI_API_TOKEN_HERE"
+#     model_name: "gpt-4-vision-preview"
+#   claude:
+#     api_url: "https://api.anthropic.com/v1/messages"
+#     api_token: "YOUR_ANTHROPIC_API_TOKEN_HERE"
+#     provider: "anthropic"
+#     model_name: "claude-3-5-sonnet-latest"
 "#;
         fs::write(config_path, default_config_content)
             .with_context(|| format!("Failed to write default config file to {:?}", config_path))?;
@@ -70,9 +128,11 @@ api_token: "YOUR_API_TOKEN_HERE"
     let config_content = fs::read_to_string(config_path)
         .with_context(|| format!("Failed to read config file from {:?}", config_path))?;
 
-    let config: Config = serde_yaml::from_str(&config_content)
+    let config_file: ConfigFile = serde_yaml::from_str(&config_content)
         .with_context(|| format!("Failed to parse YAML config file at {:?}", config_path))?;
 
+    let config = resolve_profile(config_file, profile_name, config_path)?;
+
     if config.api_token == "YOUR_API_TOKEN_HERE" || config.api_token.is_empty() {
         return Err(anyhow!(
             "Please replace 'YOUR_API_TOKEN_HERE' with your actual API token in {:?}",
@@ -85,6 +145,8 @@ api_token: "YOUR_API_TOKEN_HERE"
         model_name: config.model_name.or(Some("gpt-4-vision-preview".to_string())),
         max_tokens: config.max_tokens.or(Some(1024)),
         request_timeout_seconds: config.request_timeout_seconds.or(Some(60)),
+        max_image_dimension: config.max_image_dimension.or(Some(DEFAULT_MAX_IMAGE_DIMENSION)),
+        max_retries: config.max_retries.or(Some(DEFAULT_MAX_RETRIES)),
         ..config // Keep other fields as they were
     };
 
@@ -92,6 +154,40 @@ api_token: "YOUR_API_TOKEN_HERE"
     Ok(config)
 }
 
+/// Picks the right [`Config`] out of a parsed `config.yaml`: selects `profile_name` (falling
+/// back to `default_profile:`) out of a `profiles:` map, or returns the config as-is for the
+/// legacy flat layout (warning if `--profile` was given but has nothing to select).
+fn resolve_profile(config_file: ConfigFile, profile_name: Option<&str>, config_path: &Path) -> Result<Config> {
+    match config_file {
+        ConfigFile::Profiles {
+            mut profiles,
+            default_profile,
+        } => {
+            let selected_name = profile_name
+                .map(str::to_string)
+                .or(default_profile)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "{:?} defines 'profiles' but no --profile was given and no 'default_profile' is set",
+                        config_path
+                    )
+                })?;
+            profiles.remove(&selected_name).ok_or_else(|| {
+                anyhow!("Profile '{}' not found in {:?}", selected_name, config_path)
+            })
+        }
+        ConfigFile::Flat(config) => {
+            if let Some(name) = profile_name {
+                warn!(
+                    "--profile '{}' was given but {:?} uses the legacy flat layout (no profiles); ignoring.",
+                    name, config_path
+                );
+            }
+            Ok(config)
+        }
+    }
+}
+
 // --- Command Line Arguments ---
 
 #[derive(Parser, Debug)]
@@ -104,92 +200,72 @@ struct Cli {
     /// Optional: Path to the configuration file
     #[arg(long)]
     config: Option<PathBuf>,
-}
-
-// --- OpenAI API Interaction ---
-
-#[derive(Serialize)]
-struct ApiRequest<'a> {
-    model: &'a str,
-    messages: Vec<Message<'a>>,
-    max_tokens: u32,
-}
-
-#[derive(Serialize)]
-struct Message<'a> {
-    role: &'a str,
-    content: Vec<Content<'a>>,
-}
-
-#[derive(Serialize)]
-#[serde(tag = "type")]
-enum Content<'a> {
-    #[serde(rename = "text")]
-    Text { text: &'a str },
-    #[serde(rename = "image_url")]
-    ImageUrl { image_url: ImageUrl<'a> },
-}
-
-#[derive(Serialize)]
-struct ImageUrl<'a> {
-    url: Cow<'a, str>, // Use Cow for efficiency, avoids allocation if url is static
-    #[serde(skip_serializing_if = "Option::is_none")]
-    detail: Option<&'a str>, // Optional: can be "low", "high", "auto"
-}
-
-#[derive(Deserialize, Debug)]
-struct ApiResponse {
-    choices: Vec<Choice>,
-    #[serde(default)] // Handle cases where 'usage' might be missing
-    usage: Option<Usage>,
-    #[serde(default)] // Capture potential errors from the API
-    error: Option<ApiError>,
-}
-
-#[derive(Deserialize, Debug)]
-struct ApiError {
-    message: String,
-    #[serde(rename = "type")]
-    error_type: String,
-}
 
+    /// Optional: Name of the config profile to use (see the 'profiles:' key in config.yaml)
+    #[arg(long)]
+    profile: Option<String>,
 
-#[derive(Deserialize, Debug)]
-struct Choice {
-    message: ResponseMessage,
-    finish_reason: Option<String>,
-}
+    /// Stream the extracted text token-by-token as it is generated
+    #[arg(long)]
+    stream: bool,
 
-#[derive(Deserialize, Debug)]
-struct ResponseMessage {
-    content: Option<String>, // Make content optional, API might return null
-}
+    /// Copy the extracted text back to the clipboard after extraction
+    #[arg(short, long)]
+    copy: bool,
 
-#[derive(Deserialize, Debug, Default)]
-struct Usage {
-    prompt_tokens: u32,
-    completion_tokens: u32,
-    total_tokens: u32,
+    /// Optional: Read the input image from this file instead of the clipboard (use "-" for stdin)
+    image: Option<PathBuf>,
 }
 
-async fn encode_image_to_base64(image_data: ImageData<'_>) -> Result<String> {
-    info!(
-        "Encoding image ({}x{}) to PNG and then Base64...",
-        image_data.width, image_data.height
-    );
+// --- Image Preprocessing ---
+
+/// Whether the OS clipboard hands back BGRA (rather than RGBA) pixel data.
+/// Per `arboard`'s docs: BGRA on Windows, RGBA on macOS/Linux(X11).
+#[cfg(target_os = "windows")]
+const CLIPBOARD_IS_BGRA: bool = true;
+#[cfg(not(target_os = "windows"))]
+const CLIPBOARD_IS_BGRA: bool = false;
+
+async fn encode_image_to_base64(
+    width: u32,
+    height: u32,
+    rgba_bytes: Vec<u8>,
+    swap_red_blue: bool,
+    max_dimension: u32,
+) -> Result<String> {
+    info!("Encoding image ({}x{}) to PNG and then Base64...", width, height);
+
+    // Clipboard managers are supposed to hand back raw RGBA8, but some deliver an
+    // already-encoded PNG/JPEG blob instead; fall back to decoding it properly rather
+    // than failing outright.
+    let expected_raw_len = width as usize * height as usize * 4;
+    let image_buffer: ImageBuffer<Rgba<u8>, Vec<u8>> = if rgba_bytes.len() == expected_raw_len {
+        let mut image_buffer: ImageBuffer<Rgba<u8>, Vec<u8>> = ImageBuffer::from_raw(width, height, rgba_bytes)
+            .ok_or_else(|| anyhow!("Failed to create image buffer from raw RGBA8 data"))?;
+
+        // Only raw clipboard bytes need this: arboard hands back BGRA on Windows. An
+        // already-encoded PNG/JPEG (the `else` branch below) decodes to standard RGBA
+        // regardless of platform, so swapping it here would re-introduce the color bug.
+        if swap_red_blue {
+            debug!("Swapping B/R channels (clipboard delivered BGRA on this platform)...");
+            for pixel in image_buffer.pixels_mut() {
+                pixel.0.swap(0, 2);
+            }
+        }
 
-    // Create an ImageBuffer from the raw RGBA data provided by arboard
-    // Note: arboard gives BGRA on windows, RGBA on macos/linux(x11)
-    // We assume RGBA here, might need platform-specific handling if BGRA causes issues.
-    let image_buffer: Option<ImageBuffer<Rgba<u8>, _>> =
-        ImageBuffer::from_raw(
-            image_data.width as u32,
-            image_data.height as u32,
-            image_data.bytes.into_owned(), // Convert Cow<[u8]> to Vec<u8>
+        image_buffer
+    } else {
+        debug!(
+            "Input data wasn't raw RGBA8 of the expected size ({} bytes expected, {} given); decoding as an encoded image instead.",
+            expected_raw_len,
+            rgba_bytes.len()
         );
+        image::load_from_memory(&rgba_bytes)
+            .context("Failed to interpret image data as raw RGBA8 or a decodable image format")?
+            .to_rgba8()
+    };
 
-    let image_buffer = image_buffer
-         .ok_or_else(|| anyhow!("Failed to create image buffer from clipboard data. Data length might not match dimensions, or format might not be RGBA8."))?;
+    let image_buffer = downscale_if_needed(image_buffer, max_dimension);
 
     // Encode the image buffer to PNG format in memory
     let mut png_bytes: Vec<u8> = Vec::new();
@@ -201,134 +277,86 @@ async fn encode_image_to_base64(image_data: ImageData<'_>) -> Result<String> {
         .write_to(&mut cursor, image::ImageOutputFormat::Png)
         .context("Failed to encode image to PNG format")?;
 
-    // Encode the PNG bytes to Base64
+    // Encode the PNG bytes to Base64 (providers that need a `data:` URL prefix add it themselves)
     let base64_string = BASE64_STANDARD.encode(&png_bytes);
     debug!("Base64 encoding complete, length: {}", base64_string.len());
 
-    Ok(format!("data:image/png;base64,{}", base64_string))
+    Ok(base64_string)
 }
 
-async fn call_openai_api(
-    config: &Config,
-    base64_image: &str,
-    generate_markdown: bool,
-) -> Result<String> {
-    let client = Client::builder()
-        .timeout(Duration::from_secs(
-            config.request_timeout_seconds.unwrap_or(60), // Already has default from load_config
-        ))
-        .build()
-        .context("Failed to build HTTP client")?;
+/// Downscales `image_buffer` so its longest edge is at most `max_dimension`, preserving
+/// aspect ratio. Vision APIs bill and cap by resolution, so this keeps huge screenshots
+/// from wasting tokens (or being rejected outright). Images already within the limit are
+/// returned untouched.
+fn downscale_if_needed(
+    image_buffer: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    max_dimension: u32,
+) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+    let (width, height) = image_buffer.dimensions();
+    let longest_edge = width.max(height);
+
+    if longest_edge <= max_dimension {
+        return image_buffer;
+    }
 
-    let model = config.model_name.as_deref().unwrap_or("gpt-4-vision-preview");
+    let scale = max_dimension as f64 / longest_edge as f64;
+    let new_width = ((width as f64 * scale).round() as u32).max(1);
+    let new_height = ((height as f64 * scale).round() as u32).max(1);
 
-    info!("Using '{}' model for image to text...", model);
+    info!(
+        "Downscaling image from {}x{} to {}x{} (max_image_dimension={})",
+        width, height, new_width, new_height, max_dimension
+    );
 
-    let prompt_text = if generate_markdown {
-        "Extract all text from this image accurately. If the image contains tabular data, a list, code, or other structured content, format the output as GitHub Flavored Markdown. Pay attention to formatting details like spacing in tables. Don't use any image related markdown.  Otherwise, return the plain text. Output *only* the extracted text or markdown content and nothing else. Do not include any introductory phrases or explanations.  For bullet points, use hyphens instead of bullet characters, like a normal markdown."
-    } else {
-        "Extract all text content from this image accurately. Output *only* the extracted text and nothing else. Do not include any introductory phrases."
-    };
+    image::imageops::resize(&image_buffer, new_width, new_height, FilterType::Lanczos3)
+}
 
-    let request_payload = ApiRequest {
-        model,
-        messages: vec![Message {
-            role: "user",
-            content: vec![
-                Content::Text { text: prompt_text },
-                Content::ImageUrl {
-                    image_url: ImageUrl {
-                        url: Cow::Borrowed(base64_image),
-                        detail: Some("high"), // Request high detail for better OCR
-                    },
-                },
-            ],
-        }],
-        max_tokens: config.max_tokens.unwrap_or(1024), // Should have default
+/// Reads an image from `path` (or stdin, if `path` is `-`) and decodes it with the `image`
+/// crate, returning raw RGBA8 pixel data. This is the non-clipboard input path used for
+/// headless/SSH/WSL sessions where `arboard` has no graphical session to talk to.
+fn read_image_from_path_or_stdin(path: &Path) -> Result<(u32, u32, Vec<u8>)> {
+    let image_bytes = if path.as_os_str() == "-" {
+        info!("Reading input image from stdin...");
+        let mut buf = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut buf)
+            .context("Failed to read image bytes from stdin")?;
+        buf
+    } else {
+        info!("Reading input image from {:?}...", path);
+        fs::read(path).with_context(|| format!("Failed to read image file at {:?}", path))?
     };
 
-    info!("Sending request to API endpoint: {}", config.api_url);
-    debug!("Request payload model: {}", request_payload.model);
+    let decoded = image::load_from_memory(&image_bytes)
+        .context("Failed to decode input image")?
+        .to_rgba8();
 
-    let response = client
-        .post(&config.api_url)
-        .bearer_auth(&config.api_token)
-        .json(&request_payload)
-        .send()
-        .await
-        .context("Failed to send request to the API")?;
-
-    let status = response.status();
-    debug!("API response status: {}", status);
-
-    // Read the response body text first for better error reporting
-    let response_text = response
-        .text()
-        .await
-        .context("Failed to read API response body")?;
-
-
-    if !status.is_success() {
-        // Attempt to parse the error response if possible
-         match serde_json::from_str::<ApiResponse>(&response_text) {
-             Ok(api_response) if api_response.error.is_some() => {
-                 let api_error = api_response.error.unwrap(); // Safe due to check
-                 error!("API Error Response: Type: {}, Message: {}", api_error.error_type, api_error.message);
-                 return Err(anyhow!("API request failed with status {}: {} ({})", status, api_error.message, api_error.error_type));
-             }
-             _ => {
-                 // If parsing fails or no structured error, return the raw text
-                 error!("API Error Response Body: {}", response_text);
-                 return Err(anyhow!(
-                     "API request failed with status {}. Response body: {}",
-                     status,
-                     response_text
-                 ));
-             }
-         }
-    }
-
-    // Now parse the successful response
-    let api_response: ApiResponse = serde_json::from_str(&response_text)
-        .with_context(|| format!("Failed to parse successful JSON response from API. Body: {}", response_text))?;
-
-
-    if let Some(api_error) = api_response.error {
-        error!("API returned success status but included an error object: Type: {}, Message: {}", api_error.error_type, api_error.message);
-        return Err(anyhow!("API indicated an error despite success status: {} ({})", api_error.message, api_error.error_type));
-    }
-
-    if let Some(usage) = api_response.usage {
-         info!(
-             "API usage: Prompt tokens={}, Completion tokens={}, Total tokens={}",
-             usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
-         );
-     } else {
-         warn!("API response did not include usage information.");
-     }
+    Ok((decoded.width(), decoded.height(), decoded.into_raw()))
+}
 
+// --- Clipboard Fallback (headless / SSH / WSL) ---
 
-    if let Some(choice) = api_response.choices.into_iter().next() {
-        info!("Successfully received response from API.");
-        debug!(
-            "Finish reason: {:?}",
-            choice.finish_reason.unwrap_or_else(|| "N/A".to_string())
-        );
-        // Handle potential null content from API
-        match choice.message.content {
-            Some(text) => Ok(text),
-            None => {
-                warn!("API response choice message content was null.");
-                Ok(String::new()) // Return empty string if content is null
-            }
-        }
+/// Whether we're likely running over SSH (or a similar session with no graphical clipboard),
+/// in which case clipboard access should be routed through the terminal via OSC 52 instead.
+fn is_headless_session() -> bool {
+    std::env::var_os("SSH_TTY").is_some() || std::env::var_os("SSH_CONNECTION").is_some()
+}
 
-    } else {
-        warn!("API response did not contain any choices/content, although status was success.");
-        Err(anyhow!(
-            "API response did not contain any choices/content."
-        ))
-    }
+/// Writes `text` to the terminal's clipboard using the OSC 52 escape sequence
+/// (`ESC ] 52 ; c ; <base64> BEL`), which terminal emulators honor even over SSH where
+/// there is no graphical clipboard to talk to directly.
+fn write_osc52(text: &str) -> Result<()> {
+    let encoded = BASE64_STANDARD.encode(text);
+    let sequence = format!("\x1b]52;c;{}\x07", encoded);
+
+    let mut tty = fs::OpenOptions::new()
+        .write(true)
+        .open("/dev/tty")
+        .context("Failed to open /dev/tty for OSC 52 clipboard write")?;
+    tty.write_all(sequence.as_bytes())
+        .context("Failed to write OSC 52 escape sequence to the tty")?;
+
+    Ok(())
 }
 
 // --- Main Execution Logic ---
@@ -352,7 +380,7 @@ async fn main() -> Result<()> {
     info!("Using configuration file: {:?}", config_path);
 
     // Load configuration (handles creation/error message if first run)
-    let config = match load_config(&config_path) {
+    let config = match load_config(&config_path, cli.profile.as_deref()) {
          Ok(cfg) => cfg,
          Err(e) => {
              // Check if the error is the specific "please edit" message
@@ -369,61 +397,235 @@ async fn main() -> Result<()> {
      };
 
 
-    // Initialize clipboard
-    // This might fail in a headless CI environment!
-    let mut clipboard = match Clipboard::new() {
-        Ok(cb) => cb,
-        Err(e) => {
-            error!("Failed to initialize clipboard: {}. This might happen in environments without a graphical session (like some CI runners).", e);
-            return Err(anyhow!("Failed to initialize clipboard: {}", e));
+    // Acquire the input image: an explicit file/stdin argument takes priority so headless
+    // and SSH sessions (which have no graphical clipboard) can still OCR an image.
+    // Only clipboard-sourced bytes need the BGRA fixup; a file/stdin image is always
+    // decoded through the `image` crate into proper RGBA.
+    let mut swap_red_blue = false;
+    let (width, height, rgba_bytes) = if let Some(image_path) = &cli.image {
+        read_image_from_path_or_stdin(image_path)?
+    } else {
+        swap_red_blue = CLIPBOARD_IS_BGRA;
+        // Initialize clipboard
+        // This might fail in a headless CI environment!
+        let mut clipboard = match Clipboard::new() {
+            Ok(cb) => cb,
+            Err(e) => {
+                error!("Failed to initialize clipboard: {}. This might happen in environments without a graphical session (like some CI runners).", e);
+                if is_headless_session() || e.to_string().contains("no available clipboard provider") {
+                    return Err(anyhow!(
+                        "Failed to initialize clipboard: {}. This looks like a headless/SSH session \u{2014} pass an image file path or \"-\" for stdin instead.",
+                        e
+                    ));
+                }
+                return Err(anyhow!("Failed to initialize clipboard: {}", e));
+            }
+        };
+        info!("Clipboard initialized.");
+
+        // Check for image in clipboard
+        match clipboard.get_image() {
+            Ok(image_data) => {
+                info!(
+                    "Image detected in clipboard ({}x{})",
+                    image_data.width, image_data.height
+                );
+
+                // Simple check for empty image data which can happen sometimes
+                if image_data.width == 0 || image_data.height == 0 || image_data.bytes.is_empty() {
+                    warn!("Clipboard provided image data but it appears empty (0 width/height or no bytes). Skipping.");
+                    println!("ðŸ“‹ Clipboard image data is empty. Nothing to process.");
+                    return Ok(());
+                }
+
+                (
+                    image_data.width as u32,
+                    image_data.height as u32,
+                    image_data.bytes.into_owned(),
+                )
+            }
+            Err(arboard::Error::ContentNotAvailable) => {
+                info!("No image found in the clipboard.");
+                warn!("ðŸ“‹ No image found in the clipboard. Copy an image and try again.");
+                return Ok(()); // Not an error state, just nothing to do
+            }
+            Err(e) => {
+                // Handle other potential clipboard errors
+                error!("Error checking clipboard for image: {}", e);
+                if e.to_string().contains("failed to initialize") || e.to_string().contains("no available clipboard provider") {
+                    eprintln!("Error: Could not access the system clipboard. Ensure a clipboard manager is running or the environment supports it.");
+                }
+                return Err(anyhow!("Failed to get image from clipboard: {}", e));
+            }
         }
     };
-    info!("Clipboard initialized.");
-
-    // Check for image in clipboard
-    match clipboard.get_image() {
-        Ok(image_data) => {
-            info!(
-                "Image detected in clipboard ({}x{})",
-                image_data.width,
-                image_data.height
-            );
-
-            // Simple check for empty image data which can happen sometimes
-            if image_data.width == 0 || image_data.height == 0 || image_data.bytes.is_empty() {
-                 warn!("Clipboard provided image data but it appears empty (0 width/height or no bytes). Skipping.");
-                 println!("ðŸ“‹ Clipboard image data is empty. Nothing to process.");
-                 return Ok(());
+
+    // Encode image to base64
+    let max_image_dimension = config.max_image_dimension.unwrap_or(DEFAULT_MAX_IMAGE_DIMENSION);
+    let base64_image_data = encode_image_to_base64(width, height, rgba_bytes, swap_red_blue, max_image_dimension)
+        .await
+        .context("Failed to encode input image")?;
+
+    // Call the API
+    info!("â³ Processing image with AI..."); // User feedback
+    let backend = provider::build_provider(config.provider, cli.stream);
+    let extracted_text = backend
+        .extract_text(&config, &base64_image_data, cli.markdown)
+        .await
+        .context("Failed to get text from API")?;
+
+    // In streaming mode the text has already been printed incrementally. Only the OpenAI
+    // backend streams; the others ignore --stream and still need the text printed here.
+    let streamed = cli.stream && config.provider == ProviderKind::OpenAi;
+    if !streamed {
+        println!("{}", extracted_text);
+    }
+
+    if cli.copy {
+        // Over SSH/WSL there's usually no graphical clipboard for arboard to talk to, so
+        // route the copy through the terminal itself via OSC 52 instead.
+        if is_headless_session() {
+            match write_osc52(&extracted_text) {
+                Ok(()) => info!("Copied extracted text to the terminal clipboard via OSC 52."),
+                Err(e) => warn!("Failed to copy extracted text via OSC 52: {}", e),
+            }
+        } else {
+            match Clipboard::new().and_then(|mut cb| cb.set_text(extracted_text.clone())) {
+                Ok(()) => info!("Copied extracted text to the clipboard."),
+                Err(e) => {
+                    warn!(
+                        "Failed to copy extracted text to the system clipboard ({}); falling back to OSC 52.",
+                        e
+                    );
+                    if let Err(e) = write_osc52(&extracted_text) {
+                        warn!("OSC 52 fallback also failed: {}", e);
+                    }
+                }
             }
+        }
+    }
+
+    Ok(())
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            // Encode image to base64
-            let base64_image_data = encode_image_to_base64(image_data)
-                .await // <-- Ensure await here too (was already correct, but good to double-check)
-                .context("Failed to encode clipboard image")?;
+    fn decode_png(base64_png: &str) -> ImageBuffer<Rgba<u8>, Vec<u8>> {
+        let png_bytes = BASE64_STANDARD.decode(base64_png).unwrap();
+        image::load_from_memory(&png_bytes).unwrap().to_rgba8()
+    }
 
-            // Call the API
-            info!("â³ Processing image with AI..."); // User feedback
-            let extracted_text = call_openai_api(&config, &base64_image_data, cli.markdown)
-                .await
-                .context("Failed to get text from API")?;
+    #[tokio::test]
+    async fn raw_rgba_swap_red_blue() {
+        // A 2x1 raw RGBA8 buffer delivered as BGRA (red and blue swapped in the bytes).
+        let bgra_bytes = vec![0, 0, 255, 255, 0, 255, 0, 255];
+        let base64_png = encode_image_to_base64(2, 1, bgra_bytes, true, 2048)
+            .await
+            .unwrap();
+        let decoded = decode_png(&base64_png);
+        assert_eq!(decoded.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(decoded.get_pixel(1, 0).0, [0, 255, 0, 255]);
+    }
 
-            println!("{}", extracted_text);
+    #[tokio::test]
+    async fn raw_rgba_no_swap() {
+        let rgba_bytes = vec![255, 0, 0, 255, 0, 255, 0, 255];
+        let base64_png = encode_image_to_base64(2, 1, rgba_bytes, false, 2048)
+            .await
+            .unwrap();
+        let decoded = decode_png(&base64_png);
+        assert_eq!(decoded.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(decoded.get_pixel(1, 0).0, [0, 255, 0, 255]);
+    }
 
-            Ok(())
-        }
-        Err(arboard::Error::ContentNotAvailable) => {
-            info!("No image found in the clipboard.");
-            warn!("ðŸ“‹ No image found in the clipboard. Copy an image and try again.");
-            Ok(()) // Not an error state, just nothing to do
-        }
-        Err(e) => {
-            // Handle other potential clipboard errors
-            error!("Error checking clipboard for image: {}", e);
-             if e.to_string().contains("failed to initialize") || e.to_string().contains("no available clipboard provider"){
-                 eprintln!("Error: Could not access the system clipboard. Ensure a clipboard manager is running or the environment supports it.");
-             }
-            Err(anyhow!("Failed to get image from clipboard: {}", e))
+    #[tokio::test]
+    async fn encoded_input_is_not_swapped_even_when_requested() {
+        // A pre-encoded PNG blob (not raw RGBA8 of the expected length) must decode as-is;
+        // `swap_red_blue` only applies to the raw-bytes path.
+        let mut source = ImageBuffer::<Rgba<u8>, Vec<u8>>::new(2, 1);
+        source.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        source.put_pixel(1, 0, Rgba([0, 255, 0, 255]));
+        let mut png_bytes = Vec::new();
+        source
+            .write_to(&mut Cursor::new(&mut png_bytes), image::ImageOutputFormat::Png)
+            .unwrap();
+
+        let base64_png = encode_image_to_base64(2, 1, png_bytes, true, 2048)
+            .await
+            .unwrap();
+        let decoded = decode_png(&base64_png);
+        assert_eq!(decoded.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(decoded.get_pixel(1, 0).0, [0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn downscale_if_needed_preserves_aspect_ratio() {
+        let image_buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::new(200, 100);
+        let resized = downscale_if_needed(image_buffer, 50);
+        assert_eq!(resized.dimensions(), (50, 25));
+    }
+
+    #[test]
+    fn downscale_if_needed_leaves_small_images_untouched() {
+        let image_buffer = ImageBuffer::<Rgba<u8>, Vec<u8>>::new(100, 50);
+        let resized = downscale_if_needed(image_buffer, 200);
+        assert_eq!(resized.dimensions(), (100, 50));
+    }
+
+    fn sample_config(api_token: &str) -> Config {
+        Config {
+            api_url: "https://example.com".to_string(),
+            api_token: api_token.to_string(),
+            model_name: None,
+            max_tokens: None,
+            request_timeout_seconds: None,
+            provider: ProviderKind::OpenAi,
+            max_image_dimension: None,
+            detail: None,
+            proxy: None,
+            max_retries: None,
         }
     }
+
+    #[test]
+    fn resolve_profile_selects_named_profile() {
+        let config_file = ConfigFile::Profiles {
+            profiles: HashMap::from([
+                ("openai".to_string(), sample_config("openai-token")),
+                ("claude".to_string(), sample_config("claude-token")),
+            ]),
+            default_profile: None,
+        };
+        let config = resolve_profile(config_file, Some("claude"), Path::new("config.yaml")).unwrap();
+        assert_eq!(config.api_token, "claude-token");
+    }
+
+    #[test]
+    fn resolve_profile_falls_back_to_default_profile() {
+        let config_file = ConfigFile::Profiles {
+            profiles: HashMap::from([("openai".to_string(), sample_config("openai-token"))]),
+            default_profile: Some("openai".to_string()),
+        };
+        let config = resolve_profile(config_file, None, Path::new("config.yaml")).unwrap();
+        assert_eq!(config.api_token, "openai-token");
+    }
+
+    #[test]
+    fn resolve_profile_errors_when_no_profile_is_resolvable() {
+        let config_file = ConfigFile::Profiles {
+            profiles: HashMap::from([("openai".to_string(), sample_config("openai-token"))]),
+            default_profile: None,
+        };
+        let err = resolve_profile(config_file, None, Path::new("config.yaml")).unwrap_err();
+        assert!(err.to_string().contains("no --profile was given"));
+    }
+
+    #[test]
+    fn resolve_profile_warns_but_succeeds_for_legacy_flat_config_with_profile_flag() {
+        let config_file = ConfigFile::Flat(sample_config("flat-token"));
+        let config = resolve_profile(config_file, Some("claude"), Path::new("config.yaml")).unwrap();
+        assert_eq!(config.api_token, "flat-token");
+    }
 }
@@ -0,0 +1,232 @@
+//! Pluggable backends for turning an image into text.
+//!
+//! Each backend owns its own request/response shapes; callers only ever deal with a
+//! base64-encoded PNG in and plain text out via the [`Provider`] trait.
+
+use crate::Config;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use log::warn;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use std::time::Duration;
+
+mod anthropic;
+mod openai;
+
+pub use anthropic::AnthropicProvider;
+pub use openai::OpenAiProvider;
+
+#[async_trait]
+pub trait Provider {
+    /// Extracts text from a base64-encoded PNG (no `data:` URL prefix), optionally
+    /// formatted as GitHub Flavored Markdown.
+    async fn extract_text(&self, config: &Config, base64_png: &str, markdown: bool) -> Result<String>;
+}
+
+/// Which [`Provider`] implementation to use, selected via the `provider:` key in `Config`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    #[default]
+    OpenAi,
+    Anthropic,
+}
+
+/// Builds the `Provider` selected by `kind`, wiring in whether streaming output was
+/// requested on the command line (currently only the OpenAI backend can stream).
+pub fn build_provider(kind: ProviderKind, stream: bool) -> Box<dyn Provider> {
+    match kind {
+        ProviderKind::OpenAi => Box::new(OpenAiProvider { stream }),
+        ProviderKind::Anthropic => {
+            if stream {
+                warn!("--stream was requested but the Anthropic provider doesn't support streaming yet; ignoring.");
+            }
+            Box::new(AnthropicProvider)
+        }
+    }
+}
+
+/// The instruction prompt sent alongside the image; shared by every backend so the
+/// extraction behavior doesn't drift between providers.
+fn prompt_text(markdown: bool) -> &'static str {
+    if markdown {
+        "Extract all text from this image accurately. If the image contains tabular data, a list, code, or other structured content, format the output as GitHub Flavored Markdown. Pay attention to formatting details like spacing in tables. Don't use any image related markdown.  Otherwise, return the plain text. Output *only* the extracted text or markdown content and nothing else. Do not include any introductory phrases or explanations.  For bullet points, use hyphens instead of bullet characters, like a normal markdown."
+    } else {
+        "Extract all text content from this image accurately. Output *only* the extracted text and nothing else. Do not include any introductory phrases."
+    }
+}
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+/// Builds the `reqwest::Client` shared by every backend: a request timeout from `Config`,
+/// plus an optional HTTP(S) proxy from `Config::proxy` or the `HTTPS_PROXY`/`HTTP_PROXY`
+/// environment variables (checked in that order, each also in lowercase).
+fn build_http_client(config: &Config) -> Result<Client> {
+    let mut builder = Client::builder().timeout(Duration::from_secs(
+        config.request_timeout_seconds.unwrap_or(60),
+    ));
+
+    if let Some(proxy_url) = resolve_proxy_url(config) {
+        log::info!("Using HTTP proxy: {}", proxy_url);
+        let proxy = reqwest::Proxy::all(&proxy_url)
+            .with_context(|| format!("Failed to configure proxy '{}'", proxy_url))?;
+        builder = builder.proxy(proxy);
+    }
+
+    builder.build().context("Failed to build HTTP client")
+}
+
+fn resolve_proxy_url(config: &Config) -> Option<String> {
+    config
+        .proxy
+        .clone()
+        .or_else(|| std::env::var("HTTPS_PROXY").ok())
+        .or_else(|| std::env::var("https_proxy").ok())
+        .or_else(|| std::env::var("HTTP_PROXY").ok())
+        .or_else(|| std::env::var("http_proxy").ok())
+}
+
+/// Sends the request built by `build_request`, retrying with exponential backoff on
+/// connection errors, timeouts, and HTTP 429/5xx responses. A `Retry-After` header on a
+/// 429/5xx response overrides the computed backoff. Non-retryable 4xx responses (auth,
+/// bad request, ...) are returned as-is so callers can parse the structured API error.
+async fn send_with_retry<F>(build_request: F, max_retries: u32) -> Result<Response>
+where
+    F: Fn() -> RequestBuilder,
+{
+    let mut backoff = INITIAL_BACKOFF;
+
+    for attempt in 0..=max_retries {
+        match build_request().send().await {
+            Ok(response) if is_retryable_status(response.status()) && attempt < max_retries => {
+                let wait = retry_after(&response).unwrap_or(backoff);
+                warn!(
+                    "Request failed with status {} (attempt {}/{}); retrying in {:?}...",
+                    response.status(),
+                    attempt + 1,
+                    max_retries,
+                    wait
+                );
+                tokio::time::sleep(wait).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            Ok(response) => return Ok(response),
+            Err(e) if (e.is_connect() || e.is_timeout()) && attempt < max_retries => {
+                warn!(
+                    "Request error ({}) (attempt {}/{}); retrying in {:?}...",
+                    e,
+                    attempt + 1,
+                    max_retries,
+                    backoff
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            Err(e) => return Err(e).context("Failed to send request to the API"),
+        }
+    }
+
+    unreachable!("loop always returns by the time attempt == max_retries")
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn retry_after(response: &Response) -> Option<Duration> {
+    retry_after_from_headers(response.headers())
+}
+
+/// Parses a `Retry-After` header value into a wait duration. Only the delay-seconds form
+/// (e.g. `Retry-After: 120`) is supported; the HTTP-date form (e.g.
+/// `Retry-After: Wed, 21 Oct 2026 07:28:00 GMT`) falls through to `None`, and the caller's
+/// computed exponential backoff is used instead.
+fn retry_after_from_headers(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+    headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    #[test]
+    fn retryable_status_covers_rate_limit_and_server_errors() {
+        assert!(is_retryable_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable_status(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(is_retryable_status(StatusCode::SERVICE_UNAVAILABLE));
+    }
+
+    #[test]
+    fn non_retryable_status_covers_auth_and_bad_request() {
+        assert!(!is_retryable_status(StatusCode::UNAUTHORIZED));
+        assert!(!is_retryable_status(StatusCode::BAD_REQUEST));
+        assert!(!is_retryable_status(StatusCode::OK));
+    }
+
+    fn sample_config(proxy: Option<&str>) -> Config {
+        Config {
+            api_url: "https://example.com".to_string(),
+            api_token: "token".to_string(),
+            model_name: None,
+            max_tokens: None,
+            request_timeout_seconds: None,
+            provider: ProviderKind::OpenAi,
+            max_image_dimension: None,
+            detail: None,
+            proxy: proxy.map(str::to_string),
+            max_retries: None,
+        }
+    }
+
+    #[test]
+    fn resolve_proxy_url_prefers_config_over_env() {
+        let config = sample_config(Some("http://config-proxy.example.com"));
+        assert_eq!(
+            resolve_proxy_url(&config).as_deref(),
+            Some("http://config-proxy.example.com")
+        );
+    }
+
+    #[test]
+    fn resolve_proxy_url_none_when_unset() {
+        // No `proxy:` in config and (as is the case in this test process) no proxy env
+        // vars set; `resolve_proxy_url` should fall through to `None`.
+        let config = sample_config(None);
+        if std::env::var_os("HTTPS_PROXY").is_none()
+            && std::env::var_os("https_proxy").is_none()
+            && std::env::var_os("HTTP_PROXY").is_none()
+            && std::env::var_os("http_proxy").is_none()
+        {
+            assert_eq!(resolve_proxy_url(&config), None);
+        }
+    }
+
+    #[test]
+    fn retry_after_parses_delay_seconds() {
+        let mut headers = HeaderMap::new();
+        headers.insert(reqwest::header::RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after_from_headers(&headers), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn retry_after_ignores_http_date_form() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            reqwest::header::RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2026 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after_from_headers(&headers), None);
+    }
+
+    #[test]
+    fn retry_after_none_when_header_missing() {
+        assert_eq!(retry_after_from_headers(&HeaderMap::new()), None);
+    }
+}
@@ -0,0 +1,177 @@
+use super::{build_http_client, prompt_text, send_with_retry, Provider};
+use crate::Config;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use log::{debug, error, info};
+use serde::{Deserialize, Serialize};
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const DEFAULT_MODEL: &str = "claude-3-5-sonnet-latest";
+
+/// The native Anthropic Messages API backend.
+pub struct AnthropicProvider;
+
+#[async_trait]
+impl Provider for AnthropicProvider {
+    async fn extract_text(&self, config: &Config, base64_png: &str, markdown: bool) -> Result<String> {
+        let client = build_http_client(config)?;
+
+        let model = config.model_name.as_deref().unwrap_or(DEFAULT_MODEL);
+
+        info!("Using '{}' model for image to text...", model);
+
+        let request_payload = MessagesRequest {
+            model,
+            max_tokens: config.max_tokens.unwrap_or(1024),
+            messages: vec![RequestMessage {
+                role: "user",
+                content: vec![
+                    RequestContent::Image {
+                        source: ImageSource {
+                            source_type: "base64",
+                            media_type: "image/png",
+                            data: base64_png,
+                        },
+                    },
+                    RequestContent::Text {
+                        text: prompt_text(markdown),
+                    },
+                ],
+            }],
+        };
+
+        info!("Sending request to API endpoint: {}", config.api_url);
+        debug!("Request payload model: {}", request_payload.model);
+
+        let response = send_with_retry(
+            || {
+                client
+                    .post(&config.api_url)
+                    .header("x-api-key", &config.api_token)
+                    .header("anthropic-version", ANTHROPIC_VERSION)
+                    .json(&request_payload)
+            },
+            config.max_retries.unwrap_or(3),
+        )
+        .await?;
+
+        let status = response.status();
+        debug!("API response status: {}", status);
+
+        let response_text = response
+            .text()
+            .await
+            .context("Failed to read API response body")?;
+
+        if !status.is_success() {
+            match serde_json::from_str::<MessagesResponse>(&response_text) {
+                Ok(api_response) if api_response.error.is_some() => {
+                    let api_error = api_response.error.unwrap(); // Safe due to check
+                    error!("API Error Response: Type: {}, Message: {}", api_error.error_type, api_error.message);
+                    return Err(anyhow!("API request failed with status {}: {} ({})", status, api_error.message, api_error.error_type));
+                }
+                _ => {
+                    error!("API Error Response Body: {}", response_text);
+                    return Err(anyhow!(
+                        "API request failed with status {}. Response body: {}",
+                        status,
+                        response_text
+                    ));
+                }
+            }
+        }
+
+        let api_response: MessagesResponse = serde_json::from_str(&response_text)
+            .with_context(|| format!("Failed to parse successful JSON response from API. Body: {}", response_text))?;
+
+        if let Some(api_error) = api_response.error {
+            error!("API returned success status but included an error object: Type: {}, Message: {}", api_error.error_type, api_error.message);
+            return Err(anyhow!("API indicated an error despite success status: {} ({})", api_error.message, api_error.error_type));
+        }
+
+        if let Some(usage) = api_response.usage {
+            info!(
+                "API usage: Input tokens={}, Output tokens={}",
+                usage.input_tokens, usage.output_tokens
+            );
+        }
+
+        let extracted_text = api_response
+            .content
+            .into_iter()
+            .filter_map(|block| match block {
+                ResponseContent::Text { text } => Some(text),
+                ResponseContent::Other => None,
+            })
+            .collect::<Vec<_>>()
+            .join("");
+
+        if extracted_text.is_empty() {
+            error!("API response did not contain any text content blocks, although status was success.");
+        }
+
+        Ok(extracted_text)
+    }
+}
+
+#[derive(Serialize)]
+struct MessagesRequest<'a> {
+    model: &'a str,
+    max_tokens: u32,
+    messages: Vec<RequestMessage<'a>>,
+}
+
+#[derive(Serialize)]
+struct RequestMessage<'a> {
+    role: &'a str,
+    content: Vec<RequestContent<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum RequestContent<'a> {
+    #[serde(rename = "text")]
+    Text { text: &'a str },
+    #[serde(rename = "image")]
+    Image { source: ImageSource<'a> },
+}
+
+#[derive(Serialize)]
+struct ImageSource<'a> {
+    #[serde(rename = "type")]
+    source_type: &'a str,
+    media_type: &'a str,
+    data: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+struct MessagesResponse {
+    #[serde(default)]
+    content: Vec<ResponseContent>,
+    #[serde(default)]
+    usage: Option<Usage>,
+    #[serde(default)]
+    error: Option<ApiError>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type")]
+enum ResponseContent {
+    #[serde(rename = "text")]
+    Text { text: String },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiError {
+    message: String,
+    #[serde(rename = "type")]
+    error_type: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Usage {
+    input_tokens: u32,
+    output_tokens: u32,
+}
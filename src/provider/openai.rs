@@ -0,0 +1,280 @@
+use super::{build_http_client, prompt_text, send_with_retry, Provider};
+use crate::Config;
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use eventsource_stream::Eventsource;
+use futures_util::StreamExt;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, io::Write as _};
+
+/// The OpenAI-compatible chat-completions backend (also used by most self-hosted and
+/// third-party OpenAI-compatible vision endpoints).
+pub struct OpenAiProvider {
+    pub stream: bool,
+}
+
+#[async_trait]
+impl Provider for OpenAiProvider {
+    async fn extract_text(&self, config: &Config, base64_png: &str, markdown: bool) -> Result<String> {
+        let client = build_http_client(config)?;
+
+        let model = config.model_name.as_deref().unwrap_or("gpt-4-vision-preview");
+
+        info!("Using '{}' model for image to text...", model);
+
+        let image_url = format!("data:image/png;base64,{}", base64_png);
+
+        let request_payload = ApiRequest {
+            model,
+            messages: vec![Message {
+                role: "user",
+                content: vec![
+                    Content::Text {
+                        text: prompt_text(markdown),
+                    },
+                    Content::ImageUrl {
+                        image_url: ImageUrl {
+                            url: Cow::Owned(image_url),
+                            detail: Some(config.detail.as_deref().unwrap_or("high")),
+                        },
+                    },
+                ],
+            }],
+            max_tokens: config.max_tokens.unwrap_or(1024), // Should have default
+            stream: self.stream.then_some(true),
+        };
+
+        info!("Sending request to API endpoint: {}", config.api_url);
+        debug!("Request payload model: {}", request_payload.model);
+
+        let response = send_with_retry(
+            || {
+                client
+                    .post(&config.api_url)
+                    .bearer_auth(&config.api_token)
+                    .json(&request_payload)
+            },
+            config.max_retries.unwrap_or(3),
+        )
+        .await?;
+
+        let status = response.status();
+        debug!("API response status: {}", status);
+
+        if self.stream {
+            if !status.is_success() {
+                let response_text = response
+                    .text()
+                    .await
+                    .context("Failed to read API response body")?;
+                error!("API Error Response Body: {}", response_text);
+                return Err(anyhow!(
+                    "API request failed with status {}. Response body: {}",
+                    status,
+                    response_text
+                ));
+            }
+            return consume_event_stream(response).await;
+        }
+
+        // Read the response body text first for better error reporting
+        let response_text = response
+            .text()
+            .await
+            .context("Failed to read API response body")?;
+
+        if !status.is_success() {
+            // Attempt to parse the error response if possible
+            match serde_json::from_str::<ApiResponse>(&response_text) {
+                Ok(api_response) if api_response.error.is_some() => {
+                    let api_error = api_response.error.unwrap(); // Safe due to check
+                    error!("API Error Response: Type: {}, Message: {}", api_error.error_type, api_error.message);
+                    return Err(anyhow!("API request failed with status {}: {} ({})", status, api_error.message, api_error.error_type));
+                }
+                _ => {
+                    // If parsing fails or no structured error, return the raw text
+                    error!("API Error Response Body: {}", response_text);
+                    return Err(anyhow!(
+                        "API request failed with status {}. Response body: {}",
+                        status,
+                        response_text
+                    ));
+                }
+            }
+        }
+
+        // Now parse the successful response
+        let api_response: ApiResponse = serde_json::from_str(&response_text)
+            .with_context(|| format!("Failed to parse successful JSON response from API. Body: {}", response_text))?;
+
+        if let Some(api_error) = api_response.error {
+            error!("API returned success status but included an error object: Type: {}, Message: {}", api_error.error_type, api_error.message);
+            return Err(anyhow!("API indicated an error despite success status: {} ({})", api_error.message, api_error.error_type));
+        }
+
+        if let Some(usage) = api_response.usage {
+            info!(
+                "API usage: Prompt tokens={}, Completion tokens={}, Total tokens={}",
+                usage.prompt_tokens, usage.completion_tokens, usage.total_tokens
+            );
+        } else {
+            warn!("API response did not include usage information.");
+        }
+
+        if let Some(choice) = api_response.choices.into_iter().next() {
+            info!("Successfully received response from API.");
+            debug!(
+                "Finish reason: {:?}",
+                choice.finish_reason.unwrap_or_else(|| "N/A".to_string())
+            );
+            // Handle potential null content from API
+            match choice.message.content {
+                Some(text) => Ok(text),
+                None => {
+                    warn!("API response choice message content was null.");
+                    Ok(String::new()) // Return empty string if content is null
+                }
+            }
+        } else {
+            warn!("API response did not contain any choices/content, although status was success.");
+            Err(anyhow!(
+                "API response did not contain any choices/content."
+            ))
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ApiRequest<'a> {
+    model: &'a str,
+    messages: Vec<Message<'a>>,
+    max_tokens: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    role: &'a str,
+    content: Vec<Content<'a>>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type")]
+enum Content<'a> {
+    #[serde(rename = "text")]
+    Text { text: &'a str },
+    #[serde(rename = "image_url")]
+    ImageUrl { image_url: ImageUrl<'a> },
+}
+
+#[derive(Serialize)]
+struct ImageUrl<'a> {
+    url: Cow<'a, str>, // Use Cow for efficiency, avoids allocation if url is static
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<&'a str>, // Optional: can be "low", "high", "auto"
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiResponse {
+    choices: Vec<Choice>,
+    #[serde(default)] // Handle cases where 'usage' might be missing
+    usage: Option<Usage>,
+    #[serde(default)] // Capture potential errors from the API
+    error: Option<ApiError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ApiError {
+    message: String,
+    #[serde(rename = "type")]
+    error_type: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Choice {
+    message: ResponseMessage,
+    finish_reason: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ResponseMessage {
+    content: Option<String>, // Make content optional, API might return null
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct Usage {
+    prompt_tokens: u32,
+    completion_tokens: u32,
+    total_tokens: u32,
+}
+
+/// The `data:` payload of the terminal event the OpenAI streaming API sends to end the stream.
+const STREAM_DONE_SENTINEL: &str = "[DONE]";
+
+#[derive(Deserialize, Debug)]
+struct StreamChunk {
+    #[serde(default)]
+    choices: Vec<StreamChoice>,
+    #[serde(default)]
+    error: Option<ApiError>,
+}
+
+#[derive(Deserialize, Debug)]
+struct StreamChoice {
+    delta: StreamDelta,
+}
+
+#[derive(Deserialize, Debug, Default)]
+struct StreamDelta {
+    content: Option<String>,
+}
+
+/// Consumes a Server-Sent Events response from the streaming chat-completions
+/// endpoint, printing each content delta to stdout as it arrives and
+/// accumulating the full text for the caller (e.g. for `--copy`).
+async fn consume_event_stream(response: reqwest::Response) -> Result<String> {
+    let mut event_stream = response.bytes_stream().eventsource();
+    let mut accumulated_text = String::new();
+    let stdout = std::io::stdout();
+
+    while let Some(event) = event_stream.next().await {
+        let event = event.context("Failed to read event from the SSE stream")?;
+
+        if event.data == STREAM_DONE_SENTINEL {
+            break;
+        }
+
+        let chunk: StreamChunk = serde_json::from_str(&event.data).with_context(|| {
+            format!("Failed to parse SSE data as a stream chunk: {}", event.data)
+        })?;
+
+        if let Some(api_error) = chunk.error {
+            error!(
+                "API streamed an error event: Type: {}, Message: {}",
+                api_error.error_type, api_error.message
+            );
+            return Err(anyhow!(
+                "API streaming failed: {} ({})",
+                api_error.message,
+                api_error.error_type
+            ));
+        }
+
+        // The first chunk is often role-only and carries no content delta; skip it.
+        let Some(choice) = chunk.choices.into_iter().next() else {
+            continue;
+        };
+        let Some(content) = choice.delta.content else {
+            continue;
+        };
+
+        print!("{}", content);
+        stdout.lock().flush().ok();
+        accumulated_text.push_str(&content);
+    }
+
+    info!("Finished consuming event stream.");
+    Ok(accumulated_text)
+}